@@ -0,0 +1,104 @@
+//! Include/exclude filtering over fully-qualified namespace+item names
+//! (e.g. `Windows.Graphics.Dxgi.IDXGIDevice`), so wildcard imports don't
+//! pull in more features than a user actually wants counted.
+
+/// Returns whether `full_name` should be counted, given `include`/`exclude`
+/// patterns: excluded if any `exclude` pattern matches; otherwise included
+/// if `include` is empty or any `include` pattern matches.
+pub fn passes(full_name: &str, include: &[String], exclude: &[String]) -> bool {
+    if exclude
+        .iter()
+        .any(|pattern| matches_pattern(pattern, full_name))
+    {
+        return false;
+    }
+    include.is_empty()
+        || include
+            .iter()
+            .any(|pattern| matches_pattern(pattern, full_name))
+}
+
+/// A pattern containing `*` is matched as a glob; otherwise it's a prefix.
+fn matches_pattern(pattern: &str, full_name: &str) -> bool {
+    if pattern.contains('*') {
+        glob_match(pattern.as_bytes(), full_name.as_bytes())
+    } else {
+        full_name.starts_with(pattern)
+    }
+}
+
+/// Minimal backtracking glob matcher supporting `*` (matches any, including
+/// empty, run of characters). No other wildcard syntax is recognized.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            glob_match(rest, text) || (!text.is_empty() && glob_match(pattern, &text[1..]))
+        }
+        Some(&c) => !text.is_empty() && text[0] == c && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn passes_with_no_patterns() {
+        assert!(passes("Windows.Win32.Foundation.HWND", &[], &[]));
+    }
+
+    #[test]
+    fn passes_respects_prefix_include() {
+        let include = vec!["Windows.Win32.Foundation".to_string()];
+        assert!(passes("Windows.Win32.Foundation.HWND", &include, &[]));
+        assert!(!passes(
+            "Windows.Win32.Graphics.Dxgi.IDXGIDevice",
+            &include,
+            &[]
+        ));
+    }
+
+    #[test]
+    fn passes_respects_glob_include() {
+        let include = vec!["Windows.Win32.Graphics.*".to_string()];
+        assert!(passes(
+            "Windows.Win32.Graphics.Dxgi.IDXGIDevice",
+            &include,
+            &[]
+        ));
+        assert!(!passes("Windows.Win32.Foundation.HWND", &include, &[]));
+    }
+
+    #[test]
+    fn passes_exclude_overrides_include() {
+        let include = vec!["Windows.Win32.*".to_string()];
+        let exclude = vec!["Windows.Win32.Graphics.*".to_string()];
+        assert!(passes("Windows.Win32.Foundation.HWND", &include, &exclude));
+        assert!(!passes(
+            "Windows.Win32.Graphics.Dxgi.IDXGIDevice",
+            &include,
+            &exclude
+        ));
+    }
+
+    #[test]
+    fn glob_match_star_matches_empty_run() {
+        assert!(glob_match(b"Windows.*", b"Windows."));
+        assert!(glob_match(b"Windows.*", b"Windows.Win32.Foundation"));
+        assert!(!glob_match(b"Windows.*", b"Win32.Foundation"));
+    }
+
+    #[test]
+    fn glob_match_star_in_middle() {
+        assert!(glob_match(
+            b"Windows.*.Foundation",
+            b"Windows.Win32.Foundation"
+        ));
+        assert!(!glob_match(
+            b"Windows.*.Foundation",
+            b"Windows.Win32.Graphics"
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+//! Finds `use windows::...` imports by parsing each `.rs` file under a
+//! directory as a real Rust AST (via `syn`) instead of scraping `rg` output.
+//! This correctly resolves grouped imports (`use windows::Win32::{A, B}`),
+//! nested braces, aliased imports (`use windows::A::B as C`), and imports
+//! inside nested inline modules.
+
+use color_eyre::eyre::Context;
+use color_eyre::eyre::Result;
+use std::path::Path;
+use std::path::PathBuf;
+use syn::visit::Visit;
+use syn::ItemUse;
+use syn::UseTree;
+use tracing::warn;
+use walkdir::WalkDir;
+
+/// A `windows` import resolved down to a namespace and, for non-wildcard
+/// imports, the specific item within it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedImport {
+    pub file: PathBuf,
+    pub namespace: String,
+    pub item: Option<String>,
+}
+
+/// Walks every `.rs` file under `scan_dir`, parsing it with `syn` and
+/// collecting every `use windows::...` import into a flat list of
+/// [`ResolvedImport`]s.
+pub async fn find_windows_imports(scan_dir: &Path) -> Result<Vec<ResolvedImport>> {
+    let mut results = Vec::new();
+
+    for entry in WalkDir::new(scan_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+    {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if entry.path().extension().and_then(|ext| ext.to_str()) != Some("rs") {
+            continue;
+        }
+
+        let path = entry.path().to_path_buf();
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+        let parsed = match syn::parse_file(&content) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                warn!("Failed to parse {} as Rust: {}", path.display(), err);
+                continue;
+            }
+        };
+
+        let mut collector = ImportCollector {
+            file: &path,
+            out: &mut results,
+        };
+        collector.visit_file(&parsed);
+    }
+
+    Ok(results)
+}
+
+/// Walks the full AST (not just top-level items) so `use windows::...`
+/// statements nested inside function bodies, blocks, and inline modules are
+/// all found, mirroring how the previous text-based scrape matched any line
+/// containing `use windows::` regardless of nesting depth.
+struct ImportCollector<'a> {
+    file: &'a Path,
+    out: &'a mut Vec<ResolvedImport>,
+}
+
+impl<'ast> Visit<'ast> for ImportCollector<'_> {
+    fn visit_item_use(&mut self, item_use: &'ast ItemUse) {
+        flatten_use_tree(&item_use.tree, Vec::new(), self.file, self.out);
+        syn::visit::visit_item_use(self, item_use);
+    }
+}
+
+/// Recursively flattens a `UseTree`, accumulating path segments, and emits a
+/// [`ResolvedImport`] for every leaf (`Name`, `Rename`, or `Glob`) whose path
+/// is rooted at `windows`.
+fn flatten_use_tree(
+    tree: &UseTree,
+    mut segments: Vec<String>,
+    file: &Path,
+    out: &mut Vec<ResolvedImport>,
+) {
+    match tree {
+        UseTree::Path(path) => {
+            segments.push(path.ident.to_string());
+            flatten_use_tree(&path.tree, segments, file, out);
+        }
+        UseTree::Name(name) => {
+            segments.push(name.ident.to_string());
+            emit_item(segments, file, out);
+        }
+        UseTree::Rename(rename) => {
+            // Resolve against the real item name, ignoring the local alias.
+            segments.push(rename.ident.to_string());
+            emit_item(segments, file, out);
+        }
+        UseTree::Glob(_) => emit_wildcard(segments, file, out),
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                flatten_use_tree(tree, segments.clone(), file, out);
+            }
+        }
+    }
+}
+
+fn emit_item(segments: Vec<String>, file: &Path, out: &mut Vec<ResolvedImport>) {
+    if segments.first().map(String::as_str) != Some("windows") || segments.len() < 3 {
+        return;
+    }
+    let namespace = format!("Windows.{}", segments[1..segments.len() - 1].join("."));
+    let item = segments.last().cloned();
+    out.push(ResolvedImport {
+        file: file.to_path_buf(),
+        namespace,
+        item,
+    });
+}
+
+fn emit_wildcard(segments: Vec<String>, file: &Path, out: &mut Vec<ResolvedImport>) {
+    if segments.first().map(String::as_str) != Some("windows") || segments.len() < 2 {
+        return;
+    }
+    let namespace = format!("Windows.{}", segments[1..].join("."));
+    out.push(ResolvedImport {
+        file: file.to_path_buf(),
+        namespace,
+        item: None,
+    });
+}
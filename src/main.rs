@@ -1,18 +1,27 @@
+mod filter;
+mod imports;
+mod manifest;
+mod report;
+
 use clap::Arg;
 use clap::Command;
 use color_eyre::eyre::eyre;
 use color_eyre::eyre::Result;
 use color_eyre::eyre::WrapErr;
 use directories::ProjectDirs;
+use imports::ResolvedImport;
 use itertools::Itertools;
+use manifest::WriteMode;
+use report::FeatureReport;
+use report::ImportOutcome;
+use report::ImportReport;
 use serde::Deserialize;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::path::Path;
 use std::path::PathBuf;
-use tokio::process::Command as TokioCommand;
+use toml_edit::DocumentMut;
 use tracing::debug;
-use tracing::error;
 use tracing::info;
 use tracing::warn;
 use tracing_subscriber::fmt;
@@ -62,11 +71,75 @@ async fn main() -> Result<()> {
                 .help("Suppress all output except the final list of features")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("write")
+                .long("write")
+                .help("Rewrite the nearest Cargo.toml's dependency features to match the computed set")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("check"),
+        )
+        .arg(
+            Arg::new("check")
+                .long("check")
+                .help("Verify the nearest Cargo.toml already declares exactly the required features; exits non-zero on drift")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("write"),
+        )
+        .arg(
+            Arg::new("dependency_name")
+                .long("dependency-name")
+                .value_name("NAME")
+                .help("Name of the dependency to update in Cargo.toml")
+                .default_value("windows"),
+        )
+        .arg(
+            Arg::new("merge")
+                .long("merge")
+                .help("Union computed features with those already declared (default)")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("replace"),
+        )
+        .arg(
+            Arg::new("replace")
+                .long("replace")
+                .help("Replace declared features with exactly the computed set")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("merge"),
+        )
+        .arg(
+            Arg::new("windows_version")
+                .long("windows-version")
+                .value_name("SEMVER")
+                .help("windows-rs version to fetch features.json for (auto-detected from Cargo.lock if omitted)"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Output format")
+                .value_parser(["text", "json"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .value_name("PATTERN")
+                .help("Only count namespaces/items matching this glob or prefix (repeatable)")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .value_name("PATTERN")
+                .help("Exclude namespaces/items matching this glob or prefix (repeatable)")
+                .action(clap::ArgAction::Append),
+        )
         .get_matches();
 
     let debug_enabled = matches.get_flag("debug");
     let quiet = matches.get_flag("quiet");
     let scan_dir = PathBuf::from(matches.get_one::<String>("scan_dir").unwrap());
+    let dependency_name = matches.get_one::<String>("dependency_name").unwrap();
 
     // Setup tracing for logging
     {
@@ -79,7 +152,7 @@ async fn main() -> Result<()> {
         };
 
         tracing_subscriber::registry()
-            .with(fmt::layer().without_time())
+            .with(fmt::layer().without_time().with_writer(std::io::stderr))
             .with(filter)
             .init();
     }
@@ -89,81 +162,376 @@ async fn main() -> Result<()> {
     debug!("Quiet mode: {}", quiet);
     debug!("Scan directory: {}", scan_dir.display());
 
-    // Run ripgrep to find windows imports
-    let imports = find_imports(&scan_dir).await?;
+    let windows_version = resolve_windows_version(
+        matches
+            .get_one::<String>("windows_version")
+            .map(String::as_str),
+        &scan_dir,
+        dependency_name,
+    )
+    .await?;
+    debug!("Using windows-rs version: {}", windows_version);
+
+    let imports = imports::find_windows_imports(&scan_dir).await?;
     if imports.is_empty() {
         warn!("No 'use windows::' imports found.");
     }
 
-    let required_features = get_required_features(imports).await?;
+    let include = matches
+        .get_many::<String>("include")
+        .map(|values| values.cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let exclude = matches
+        .get_many::<String>("exclude")
+        .map(|values| values.cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let report = get_required_features(imports, &windows_version, &include, &exclude).await?;
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        if !quiet {
+            eprintln!("Required windows-rs features:");
+        }
+        for f in &report.features {
+            println!("{}", f);
+        }
+    }
 
-    // Print required features
-    if !quiet {
-        eprintln!("Required windows-rs features:");
+    if matches.get_flag("write") {
+        let mode = if matches.get_flag("replace") {
+            WriteMode::Replace
+        } else {
+            WriteMode::Merge
+        };
+        write_manifest(&scan_dir, dependency_name, &report.features, mode).await?;
     }
-    for f in &required_features {
-        println!("{}", f);
+
+    if matches.get_flag("check") {
+        check_manifest(&scan_dir, dependency_name, &report.features).await?;
     }
 
     Ok(())
 }
 
-async fn get_required_features(imports: Vec<String>) -> Result<BTreeSet<String>> {
-    let item_to_features = load_feature_mapping().await?;
+/// Verifies that the nearest Cargo.toml's `dependency_name` dependency
+/// declares exactly `required`, printing a `+`/`-` diff and returning an
+/// error (for a non-zero exit) when it doesn't.
+async fn check_manifest(
+    scan_dir: &Path,
+    dependency_name: &str,
+    required: &BTreeSet<String>,
+) -> Result<()> {
+    let cargo_toml_path = manifest::find_cargo_toml(scan_dir)?;
+    let raw = tokio::fs::read_to_string(&cargo_toml_path)
+        .await
+        .wrap_err_with(|| format!("Failed to read {}", cargo_toml_path.display()))?;
+    let doc: DocumentMut = raw
+        .parse()
+        .wrap_err_with(|| format!("Failed to parse {}", cargo_toml_path.display()))?;
+
+    let declared = manifest::read_features(&doc, dependency_name);
+    let drift = manifest::check_features(&declared, required);
+
+    if drift.is_clean() {
+        info!(
+            "{} already declares exactly the required features for `{}`",
+            cargo_toml_path.display(),
+            dependency_name
+        );
+        return Ok(());
+    }
+
+    eprintln!(
+        "Feature drift in {} for `{}`:",
+        cargo_toml_path.display(),
+        dependency_name
+    );
+    for feature in &drift.missing {
+        eprintln!("+ {}", feature);
+    }
+    for feature in &drift.superfluous {
+        eprintln!("- {}", feature);
+    }
+
+    Err(eyre!(
+        "`{}` features in {} are out of sync with actual usage",
+        dependency_name,
+        cargo_toml_path.display()
+    ))
+}
+
+/// Determines which windows-rs version's `features.json` to use: the
+/// explicit `--windows-version` flag if given, otherwise the version
+/// resolved for `dependency_name` in the nearest `Cargo.lock`.
+async fn resolve_windows_version(
+    explicit: Option<&str>,
+    scan_dir: &Path,
+    dependency_name: &str,
+) -> Result<String> {
+    if let Some(version) = explicit {
+        return Ok(version.to_string());
+    }
+
+    let cargo_lock_path = manifest::find_cargo_lock(scan_dir)
+        .wrap_err("No --windows-version given and could not auto-detect one from a Cargo.lock")?;
+    let raw = tokio::fs::read_to_string(&cargo_lock_path)
+        .await
+        .wrap_err_with(|| format!("Failed to read {}", cargo_lock_path.display()))?;
+    let doc: DocumentMut = raw
+        .parse()
+        .wrap_err_with(|| format!("Failed to parse {}", cargo_lock_path.display()))?;
+
+    manifest::resolve_locked_version(&doc, dependency_name).ok_or_else(|| {
+        eyre!(
+            "Could not find a locked version for `{}` in {}",
+            dependency_name,
+            cargo_lock_path.display()
+        )
+    })
+}
+
+/// Locates the nearest `Cargo.toml` above `scan_dir` and rewrites its
+/// `dependency_name` dependency's `features` array to match `features`.
+async fn write_manifest(
+    scan_dir: &Path,
+    dependency_name: &str,
+    features: &BTreeSet<String>,
+    mode: WriteMode,
+) -> Result<()> {
+    let cargo_toml_path = manifest::find_cargo_toml(scan_dir)?;
+    let raw = tokio::fs::read_to_string(&cargo_toml_path)
+        .await
+        .wrap_err_with(|| format!("Failed to read {}", cargo_toml_path.display()))?;
+    let mut doc: DocumentMut = raw
+        .parse()
+        .wrap_err_with(|| format!("Failed to parse {}", cargo_toml_path.display()))?;
+
+    match manifest::write_features(&mut doc, dependency_name, features, mode)? {
+        manifest::WriteOutcome::Updated => {
+            tokio::fs::write(&cargo_toml_path, doc.to_string())
+                .await
+                .wrap_err_with(|| format!("Failed to write {}", cargo_toml_path.display()))?;
+            info!(
+                "Updated {} with {} feature(s) for dependency `{}`",
+                cargo_toml_path.display(),
+                features.len(),
+                dependency_name
+            );
+        }
+        manifest::WriteOutcome::AlreadyUpToDate => {
+            info!(
+                "{} already declares exactly the required features for `{}`",
+                cargo_toml_path.display(),
+                dependency_name
+            );
+        }
+        manifest::WriteOutcome::NotFound => {
+            return Err(eyre!(
+                "`{}` is not declared as a dependency in {}",
+                dependency_name,
+                cargo_toml_path.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+async fn get_required_features(
+    imports: Vec<ResolvedImport>,
+    windows_version: &str,
+    include: &[String],
+    exclude: &[String],
+) -> Result<FeatureReport> {
+    let item_to_features = load_feature_mapping(windows_version).await?;
+    let other_cached_versions = load_other_cached_feature_mappings(windows_version).await?;
     let mut required_features = BTreeSet::new();
+    let mut import_reports = Vec::with_capacity(imports.len());
 
     for import in imports {
-        let (_file_path, import_line) = parse_import_line(&import)?;
-        if let Some((namespace, item_opt)) = parse_namespace_and_item(&import_line) {
-            if let Some(item) = item_opt {
-                // Specific import: Look up using fully qualified name
-                let full_name = format!("{}.{}", namespace, item);
-                if let Some(features) = item_to_features.get(&full_name) {
-                    required_features.extend(features.clone());
-                } else if let Some(correct_feats) =
-                    attempt_fix_import(&item_to_features, &full_name)
-                {
-                    required_features.extend(correct_feats);
-                } else {
-                    warn!(
-                        "No features found for item: {} (import: {})",
-                        full_name, import_line
-                    );
+        let outcome = if let Some(item) = import.item.clone() {
+            // Specific import: Look up using fully qualified name
+            let full_name = format!("{}.{}", import.namespace, item);
+            if !filter::passes(&full_name, include, exclude) {
+                ImportOutcome::Filtered
+            } else if let Some(features) = item_to_features.get(&full_name) {
+                required_features.extend(features.clone());
+                ImportOutcome::Resolved {
+                    features: features.clone(),
                 }
             } else {
-                // Wildcard import: Gather all items under this namespace
-                let namespace_prefix = format!("{}.", namespace);
-                info!("Processing wildcard import for namespace: {}", namespace);
-                let matching_features = item_to_features
-                    .iter()
-                    .filter_map(|(fullname, feats)| {
-                        if fullname.starts_with(&namespace_prefix) {
-                            Some(feats.clone())
-                        } else {
-                            None
+                match attempt_fix_import(&item_to_features, &full_name) {
+                    ImportCorrection::Applied {
+                        corrected_to,
+                        features,
+                    } => {
+                        required_features.extend(features.clone());
+                        ImportOutcome::Corrected {
+                            corrected_to,
+                            features,
+                        }
+                    }
+                    ImportCorrection::Suggestions(suggestions) => {
+                        let resolved_under_other_versions =
+                            versions_resolving(&other_cached_versions, &full_name);
+                        warn!(
+                            "No exact match for {} with windows-rs {} (import in {}). Did you mean {}?{}",
+                            full_name,
+                            windows_version,
+                            import.file.display(),
+                            suggestions.iter().map(|s| format!("`{}`", s)).join(", "),
+                            version_churn_note(&resolved_under_other_versions)
+                        );
+                        ImportOutcome::Unresolved {
+                            suggestions,
+                            resolved_under_other_versions,
+                        }
+                    }
+                    ImportCorrection::None => {
+                        let resolved_under_other_versions =
+                            versions_resolving(&other_cached_versions, &full_name);
+                        warn!(
+                            "No features found for item: {} with windows-rs {} (import in {}){}",
+                            full_name,
+                            windows_version,
+                            import.file.display(),
+                            version_churn_note(&resolved_under_other_versions)
+                        );
+                        ImportOutcome::Unresolved {
+                            suggestions: Vec::new(),
+                            resolved_under_other_versions,
                         }
-                    })
-                    .flatten()
-                    .collect::<BTreeSet<_>>();
-
-                if matching_features.is_empty() {
-                    warn!("No features found for namespace: {}", namespace);
-                } else {
-                    required_features.extend(matching_features);
+                    }
                 }
             }
         } else {
-            warn!(
-                "Could not determine namespace and item for import: {}",
-                import
+            // Wildcard import: Gather all items under this namespace
+            let namespace_prefix = format!("{}.", import.namespace);
+            info!(
+                "Processing wildcard import for namespace: {}",
+                import.namespace
             );
+            let namespace_has_any_items = item_to_features
+                .keys()
+                .any(|fullname| fullname.starts_with(&namespace_prefix));
+
+            let matching_features = item_to_features
+                .iter()
+                .filter_map(|(fullname, feats)| {
+                    if fullname.starts_with(&namespace_prefix)
+                        && filter::passes(fullname, include, exclude)
+                    {
+                        Some(feats.clone())
+                    } else {
+                        None
+                    }
+                })
+                .flatten()
+                .collect::<BTreeSet<_>>();
+
+            if matching_features.is_empty() && !namespace_has_any_items {
+                warn!("No features found for namespace: {}", import.namespace);
+                ImportOutcome::EmptyWildcard
+            } else if matching_features.is_empty() {
+                warn!(
+                    "All items under namespace {} were excluded by --include/--exclude filtering",
+                    import.namespace
+                );
+                ImportOutcome::Filtered
+            } else {
+                required_features.extend(matching_features.clone());
+                ImportOutcome::Resolved {
+                    features: matching_features,
+                }
+            }
+        };
+
+        import_reports.push(ImportReport {
+            file: import.file,
+            namespace: import.namespace,
+            item: import.item,
+            outcome,
+        });
+    }
+
+    Ok(FeatureReport {
+        windows_version: windows_version.to_string(),
+        imports: import_reports,
+        features: required_features,
+    })
+}
+
+/// The other windows-rs versions (among `other_cached_versions`) under which
+/// `full_name` resolves to features, for cross-version diagnostics.
+fn versions_resolving(
+    other_cached_versions: &BTreeMap<String, BTreeMap<String, BTreeSet<String>>>,
+    full_name: &str,
+) -> Vec<String> {
+    other_cached_versions
+        .iter()
+        .filter(|(_, item_to_features)| item_to_features.contains_key(full_name))
+        .map(|(version, _)| version.clone())
+        .collect()
+}
+
+fn version_churn_note(resolved_under_other_versions: &[String]) -> String {
+    if resolved_under_other_versions.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " (resolves under windows-rs {})",
+            resolved_under_other_versions.join(", ")
+        )
+    }
+}
+
+/// Loads the feature mapping for every other windows-rs version whose
+/// `features.json` is already cached locally (never triggers a download),
+/// so unresolved items can be cross-checked against them for diagnostics
+/// about feature churn across releases.
+async fn load_other_cached_feature_mappings(
+    current_version: &str,
+) -> Result<BTreeMap<String, BTreeMap<String, BTreeSet<String>>>> {
+    let project_dirs = ProjectDirs::from("ca", "teamdman", "windows-features")
+        .ok_or_else(|| eyre!("Could not determine project directories"))?;
+    let data_dir = project_dirs.data_dir();
+    if !data_dir.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let mut mappings = BTreeMap::new();
+    let mut entries = tokio::fs::read_dir(data_dir)
+        .await
+        .wrap_err_with(|| format!("Failed to read {}", data_dir.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let Some(version) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("features-"))
+            .and_then(|name| name.strip_suffix(".json"))
+        else {
+            continue;
+        };
+        if version == current_version {
+            continue;
         }
+
+        let data = tokio::fs::read_to_string(&path)
+            .await
+            .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+        let Ok(parsed) = serde_json::from_str::<FeaturesFile>(&data) else {
+            continue;
+        };
+        mappings.insert(version.to_string(), build_feature_mappings(&parsed)?);
     }
 
-    Ok(required_features)
+    Ok(mappings)
 }
 
-async fn load_feature_mapping() -> Result<BTreeMap<String, BTreeSet<String>>> {
+async fn load_feature_mapping(windows_version: &str) -> Result<BTreeMap<String, BTreeSet<String>>> {
     // Determine project directories for storing data
     let project_dirs = ProjectDirs::from("ca", "teamdman", "windows-features")
         .ok_or_else(|| eyre!("Could not determine project directories"))?;
@@ -172,41 +540,53 @@ async fn load_feature_mapping() -> Result<BTreeMap<String, BTreeSet<String>>> {
         .await
         .wrap_err("Failed to create data directory")?;
 
-    let features_file = data_dir.join("features.json");
-    let features = load_or_download_features_file(&features_file).await?;
+    let features_file = data_dir.join(format!("features-{}.json", windows_version));
+    let features = load_or_download_features_file(&features_file, windows_version).await?;
     debug!(
-        "Loaded features.json with {} namespaces",
+        "Loaded features-{}.json with {} namespaces",
+        windows_version,
         features.namespace_map.len()
     );
     let item_to_features = build_feature_mappings(&features)?;
     Ok(item_to_features)
 }
 
-/// Downloads or loads the features.json file
-async fn load_or_download_features_file(path: &Path) -> Result<FeaturesFile> {
+/// Downloads or loads the features.json file for `windows_version`, caching
+/// it at `path` so multiple versions can coexist locally.
+async fn load_or_download_features_file(
+    path: &Path,
+    windows_version: &str,
+) -> Result<FeaturesFile> {
     if path.exists() {
-        info!("features.json already exists locally at {}", path.display());
+        info!("{} already exists locally", path.display());
         let data = tokio::fs::read_to_string(path)
             .await
-            .wrap_err("Failed to read features.json")?;
-        let parsed: FeaturesFile =
-            serde_json::from_str(&data).wrap_err("Failed to parse features.json")?;
+            .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+        let parsed: FeaturesFile = serde_json::from_str(&data)
+            .wrap_err_with(|| format!("Failed to parse {}", path.display()))?;
         return Ok(parsed);
     }
 
-    let url = "https://raw.githubusercontent.com/microsoft/windows-rs/0.58.0/crates/libs/windows/features.json";
+    let url = format!(
+        "https://raw.githubusercontent.com/microsoft/windows-rs/{}/crates/libs/windows/features.json",
+        windows_version
+    );
     info!("Downloading features.json from {}", url);
-    let resp = reqwest::get(url)
+    let resp = reqwest::get(&url)
         .await
-        .wrap_err("Failed to download features.json")?
+        .wrap_err_with(|| format!("Failed to download features.json for {}", windows_version))?
         .text()
         .await
         .wrap_err("Failed to read response body")?;
-    let parsed: FeaturesFile =
-        serde_json::from_str(&resp).wrap_err("Failed to parse downloaded features.json")?;
+    let parsed: FeaturesFile = serde_json::from_str(&resp).wrap_err_with(|| {
+        format!(
+            "Failed to parse downloaded features.json for {}",
+            windows_version
+        )
+    })?;
     tokio::fs::write(path, &resp)
         .await
-        .wrap_err("Failed to write features.json")?;
+        .wrap_err_with(|| format!("Failed to write {}", path.display()))?;
     Ok(parsed)
 }
 
@@ -243,128 +623,259 @@ fn build_feature_mappings(features: &FeaturesFile) -> Result<BTreeMap<String, BT
     Ok(item_to_features)
 }
 
-/// Runs ripgrep to find imports and returns a Vec of lines matching `use windows::`
-/// The expected format is `file_path:use windows::...;`
-async fn find_imports(scan_dir: &PathBuf) -> Result<Vec<String>> {
-    // rg "use windows::" --type rust --no-heading --no-line-number
-    let output = TokioCommand::new("rg")
-        .arg("use windows::")
-        .arg("--type")
-        .arg("rust")
-        .arg("--no-heading")
-        .arg("--no-line-number")
-        .arg("--with-filename") // Include filenames in the output
-        .arg(scan_dir)
-        .output()
-        .await
-        .wrap_err("Failed to execute ripgrep (rg)")?;
-
-    if !output.status.success() && !output.stdout.is_empty() {
-        error!("rg command returned non-zero exit code");
-    }
+/// Outcome of [`attempt_fix_import`].
+#[derive(Debug)]
+enum ImportCorrection {
+    /// An unambiguous single best match was found and applied.
+    Applied {
+        corrected_to: String,
+        features: BTreeSet<String>,
+    },
+    /// No match was unambiguous enough to auto-apply; these are the
+    /// closest candidates, for a "did you mean" message.
+    Suggestions(Vec<String>),
+    /// Nothing within the distance threshold.
+    None,
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let lines = stdout
-        .lines()
-        .map(|l| l.trim().to_string())
-        .unique()
+/// Attempt to fix a mis-namespaced or mistyped import by searching for the
+/// closest known item name by Levenshtein distance. Candidates within the
+/// threshold are ranked by ascending distance, then by shared namespace
+/// prefix length. Only an unambiguous best match (distance 0-1, and not
+/// tied with the next-best candidate) is auto-applied; otherwise the top
+/// few candidates are surfaced as "did you mean" suggestions.
+fn attempt_fix_import(
+    item_to_features: &BTreeMap<String, BTreeSet<String>>,
+    full_name: &str,
+) -> ImportCorrection {
+    let Some(item_segment) = full_name.split('.').next_back() else {
+        return ImportCorrection::None;
+    };
+    let threshold = (item_segment.chars().count() / 3).max(2);
+    let item_len = item_segment.chars().count();
+
+    // A full Levenshtein computation is O(n*m); skip it for candidates whose
+    // length alone already puts them outside the threshold (distance is
+    // always >= the difference in lengths), since item_to_features can hold
+    // tens of thousands of entries for a broad windows-rs surface.
+    let mut candidates: Vec<(&str, usize, usize)> = item_to_features
+        .keys()
+        .filter_map(|existing_item| {
+            let existing_segment = existing_item.split('.').next_back()?;
+            if existing_segment.chars().count().abs_diff(item_len) > threshold {
+                return None;
+            }
+            let distance = levenshtein_distance(item_segment, existing_segment);
+            if distance <= threshold {
+                let prefix_len = shared_prefix_len(full_name, existing_item);
+                Some((existing_item.as_str(), distance, prefix_len))
+            } else {
+                None
+            }
+        })
         .collect();
-    Ok(lines)
-}
 
-/// Parses a line from ripgrep output into file path and import line.
-/// Expected input format: `file_path:use windows::...;`
-fn parse_import_line(line: &str) -> Result<(String, String)> {
-    let parts: Vec<&str> = line.splitn(2, ':').collect();
-    if parts.len() != 2 {
-        return Err(eyre!("Invalid import line format: {}", line));
+    if candidates.is_empty() {
+        return ImportCorrection::None;
     }
-    let file_path = parts[0].to_string();
-    let import_line = parts[1].to_string();
-    Ok((file_path, import_line))
-}
 
-/// Given an import line, reconstruct the namespace and extract the item name.
-/// Supports both specific imports and wildcard imports (`::*`).
-fn parse_namespace_and_item(import_line: &str) -> Option<(String, Option<String>)> {
-    let line = import_line.trim_end_matches(';').trim();
-    let parts: Vec<&str> = line.split("::").collect();
-    if parts.len() < 3 {
-        return None;
+    candidates.sort_by(|a, b| a.1.cmp(&b.1).then(b.2.cmp(&a.2)));
+
+    let is_unambiguous = candidates[0].1 <= 1
+        && candidates
+            .get(1)
+            .is_none_or(|next| next.1 > candidates[0].1);
+
+    if is_unambiguous {
+        let (best_match, ..) = candidates[0];
+        warn!(
+            "Corrected {} to unambiguous match: {}",
+            full_name, best_match
+        );
+        return ImportCorrection::Applied {
+            corrected_to: best_match.to_string(),
+            features: item_to_features
+                .get(best_match)
+                .cloned()
+                .unwrap_or_default(),
+        };
     }
 
-    if *parts.last()? == "*" {
-        // Wildcard import
-        let namespace_parts = &parts[1..parts.len() - 1];
-        let namespace = format!("Windows.{}", namespace_parts.join("."));
-        return Some((namespace, None));
-    }
+    ImportCorrection::Suggestions(
+        candidates
+            .iter()
+            .take(3)
+            .map(|(name, ..)| name.to_string())
+            .collect(),
+    )
+}
 
-    // Specific import
-    let namespace_parts = &parts[1..parts.len() - 1];
-    let item = parts.last()?.to_string();
-    let namespace = format!("Windows.{}", namespace_parts.join("."));
+/// Standard two-row dynamic-programming Levenshtein edit distance, O(n*m)
+/// time and O(min(n, m)) memory.
+fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
 
-    Some((namespace, Some(item)))
-}
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0usize; b.len() + 1];
 
-/// Attempt to fix a mis-namespaced import by searching for the item name in all namespaces
-fn attempt_fix_import(
-    item_to_features: &BTreeMap<String, BTreeSet<String>>,
-    full_name: &str,
-) -> Option<BTreeSet<String>> {
-    // Attempt a relaxed matching by ignoring case or checking if the last segment matches
-    // But typically, if we got here with a fully qualified name, it might be a true mismatch.
-
-    let item_segment = full_name.split('.').last()?;
-
-    // Case-insensitive search for fallback
-    for (existing_item, feats) in item_to_features {
-        let existing_segment = existing_item.split('.').last()?;
-        if existing_segment.eq_ignore_ascii_case(item_segment) {
-            warn!(
-                "Corrected namespace for {} to match existing item: {}",
-                full_name, existing_item
-            );
-            return Some(feats.clone());
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
         }
+        std::mem::swap(&mut prev, &mut curr);
     }
 
-    None
+    prev[b.len()]
+}
+
+/// Number of leading characters shared between two fully-qualified names,
+/// used as a tiebreaker so corrections prefer nearby namespaces.
+fn shared_prefix_len(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(x, y)| x == y).count()
 }
 
 #[cfg(test)]
 mod test {
-    use crate::find_imports;
-    use crate::get_required_features;
-    use eyre::bail;
+    use crate::attempt_fix_import;
+    use crate::check_manifest;
+    use crate::levenshtein_distance;
+    use crate::shared_prefix_len;
+    use crate::ImportCorrection;
+    use std::collections::BTreeMap;
+    use std::collections::BTreeSet;
     use std::path::PathBuf;
 
-    #[tokio::test]
-    async fn test_dir() -> eyre::Result<()> {
-        // tests/**/expected.txt,test.rs
-        // we want to run the program against each fo those dirs
-        let test_container_dir = PathBuf::from("tests");
-        let mut test_dirs = tokio::fs::read_dir(&test_container_dir).await?;
-        while let Some(dir) = test_dirs.next_entry().await? {
-            let dir_path = dir.path();
-            if dir_path.is_dir() {
-                let dir_name = dir_path.file_name().unwrap().to_str().unwrap();
-                println!("Running test for {}", dir_name);
-                let expected_file = dir_path.join("expected.txt");
-                let expected = tokio::fs::read_to_string(&expected_file).await?;
-                let imports = find_imports(&dir_path).await?;
-                if imports.is_empty() {
-                    bail!("No 'use windows::' imports found.");
-                }
+    #[test]
+    fn levenshtein_distance_identical_strings() {
+        assert_eq!(levenshtein_distance("CreateFileW", "CreateFileW"), 0);
+    }
+
+    #[test]
+    fn levenshtein_distance_single_substitution() {
+        assert_eq!(levenshtein_distance("CreateFileW", "CreateFileV"), 1);
+    }
+
+    #[test]
+    fn levenshtein_distance_counts_insertions() {
+        assert_eq!(levenshtein_distance("CreateFileW", "CreateFileVV"), 2);
+    }
+
+    #[test]
+    fn shared_prefix_len_counts_matching_leading_chars() {
+        assert_eq!(
+            shared_prefix_len(
+                "Windows.Win32.Storage.FileSystem.CreateFileW",
+                "Windows.Win32.Storage.FileSystem.CreateFileA"
+            ),
+            43
+        );
+        assert_eq!(shared_prefix_len("abc", "xyz"), 0);
+    }
 
-                let required_features = get_required_features(imports).await?;
+    #[test]
+    fn attempt_fix_import_applies_unambiguous_single_typo() {
+        let mut item_to_features = BTreeMap::new();
+        item_to_features.insert(
+            "Windows.Win32.Storage.FileSystem.CreateFileW".to_string(),
+            BTreeSet::from(["Win32_Storage_FileSystem".to_string()]),
+        );
+        item_to_features.insert(
+            "Windows.Win32.System.Threading.CreateProcessW".to_string(),
+            BTreeSet::from(["Win32_System_Threading".to_string()]),
+        );
+
+        match attempt_fix_import(
+            &item_to_features,
+            "Windows.Win32.Storage.FileSystem.CreateFileV",
+        ) {
+            ImportCorrection::Applied {
+                corrected_to,
+                features,
+            } => {
+                assert_eq!(corrected_to, "Windows.Win32.Storage.FileSystem.CreateFileW");
                 assert_eq!(
-                    required_features,
-                    expected.lines().map(|s| s.to_string()).collect()
+                    features,
+                    BTreeSet::from(["Win32_Storage_FileSystem".to_string()])
                 );
             }
+            other => panic!("expected an unambiguous correction, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn attempt_fix_import_suggests_when_ambiguous() {
+        let mut item_to_features = BTreeMap::new();
+        item_to_features.insert(
+            "Windows.Win32.Storage.FileSystem.CreateFileW".to_string(),
+            BTreeSet::new(),
+        );
+        item_to_features.insert(
+            "Windows.Win32.Storage.FileSystem.CreateFileA".to_string(),
+            BTreeSet::new(),
+        );
+
+        match attempt_fix_import(
+            &item_to_features,
+            "Windows.Win32.Storage.FileSystem.CreateFileX",
+        ) {
+            ImportCorrection::Suggestions(suggestions) => {
+                assert_eq!(suggestions.len(), 2);
+            }
+            other => panic!("expected ambiguous suggestions, got {other:?}"),
         }
-        Ok(())
+    }
+
+    #[test]
+    fn attempt_fix_import_none_when_nothing_close() {
+        let mut item_to_features = BTreeMap::new();
+        item_to_features.insert(
+            "Windows.Win32.Storage.FileSystem.CreateFileW".to_string(),
+            BTreeSet::new(),
+        );
+
+        assert!(matches!(
+            attempt_fix_import(
+                &item_to_features,
+                "Windows.Win32.Graphics.Dxgi.CompletelyUnrelatedName"
+            ),
+            ImportCorrection::None
+        ));
+    }
+
+    async fn write_temp_cargo_toml(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "windows-features-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("Cargo.toml");
+        tokio::fs::write(&path, contents).await.unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn check_manifest_passes_when_declared_matches_required() -> eyre::Result<()> {
+        let dir = write_temp_cargo_toml(
+            "clean",
+            "[dependencies]\nwindows = { version = \"0.58\", features = [\"Win32_Foundation\"] }\n",
+        )
+        .await;
+        let required = BTreeSet::from(["Win32_Foundation".to_string()]);
+        check_manifest(&dir, "windows", &required).await
+    }
+
+    #[tokio::test]
+    async fn check_manifest_fails_on_drift() {
+        let dir = write_temp_cargo_toml(
+            "drift",
+            "[dependencies]\nwindows = { version = \"0.58\", features = [\"Win32_Foundation\"] }\n",
+        )
+        .await;
+        let required = BTreeSet::from(["Win32_System_Threading".to_string()]);
+        assert!(check_manifest(&dir, "windows", &required).await.is_err());
     }
 }
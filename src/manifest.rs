@@ -0,0 +1,488 @@
+//! Rewriting the `windows` dependency's `features` array in a project's
+//! `Cargo.toml` so the manifest stays in sync with the features computed
+//! from the project's imports.
+
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use color_eyre::eyre::Result;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use toml_edit::Array;
+use toml_edit::DocumentMut;
+use toml_edit::Item;
+use toml_edit::TableLike;
+use toml_edit::Value;
+use tracing::warn;
+
+/// How newly computed features should be combined with whatever the
+/// manifest already declares for the dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Union the computed features with whatever is already there.
+    Merge,
+    /// Replace the declared features with exactly the computed set.
+    Replace,
+}
+
+/// Result of [`write_features`]: whether anything changed, or why not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// The `features` array was updated in at least one table.
+    Updated,
+    /// The dependency already declared exactly the computed features.
+    AlreadyUpToDate,
+    /// `dependency_name` isn't declared in any `[dependencies]`-shaped table.
+    NotFound,
+}
+
+/// Walks upward from `start_dir` looking for the nearest `Cargo.toml`.
+pub fn find_cargo_toml(start_dir: &Path) -> Result<PathBuf> {
+    let mut dir = start_dir
+        .canonicalize()
+        .wrap_err_with(|| format!("Failed to canonicalize {}", start_dir.display()))?;
+    loop {
+        let candidate = dir.join("Cargo.toml");
+        if candidate.is_file() {
+            return Ok(candidate);
+        }
+        if !dir.pop() {
+            return Err(eyre!(
+                "Could not find a Cargo.toml above {}",
+                start_dir.display()
+            ));
+        }
+    }
+}
+
+/// Walks upward from `start_dir` looking for the nearest `Cargo.lock`
+/// (alongside the nearest `Cargo.toml`).
+pub fn find_cargo_lock(start_dir: &Path) -> Result<PathBuf> {
+    let cargo_toml = find_cargo_toml(start_dir)?;
+    let cargo_lock = cargo_toml.with_file_name("Cargo.lock");
+    if cargo_lock.is_file() {
+        Ok(cargo_lock)
+    } else {
+        Err(eyre!(
+            "No Cargo.lock found alongside {}",
+            cargo_toml.display()
+        ))
+    }
+}
+
+/// Reads the resolved version of `dependency_name` out of a parsed
+/// `Cargo.lock`'s `[[package]]` entries. Cargo.lock can carry more than one
+/// `[[package]]` entry for the same crate name when semver-incompatible
+/// versions coexist in the dependency graph, so a single match is
+/// disambiguated against the root package's own `dependencies` list (which
+/// names an exact version whenever more than one is present); if that fails
+/// too, the first match is used and a warning is raised.
+pub fn resolve_locked_version(cargo_lock: &DocumentMut, dependency_name: &str) -> Option<String> {
+    let packages = cargo_lock.get("package")?.as_array_of_tables()?;
+    let matches: Vec<&str> = packages
+        .iter()
+        .filter_map(|package| {
+            let name = package.get("name")?.as_str()?;
+            if name != dependency_name {
+                return None;
+            }
+            package.get("version")?.as_str()
+        })
+        .collect();
+
+    match matches.as_slice() {
+        [] => None,
+        [version] => Some(version.to_string()),
+        _ => {
+            if let Some(version) = resolve_via_root_dependencies(packages, dependency_name) {
+                return Some(version);
+            }
+            warn!(
+                "Multiple locked versions found for `{}` ({}); could not disambiguate via the root package's dependency list, using `{}`",
+                dependency_name,
+                matches.join(", "),
+                matches[0]
+            );
+            Some(matches[0].to_string())
+        }
+    }
+}
+
+/// Disambiguates a multi-version match by finding the root package (the one
+/// `[[package]]` entry with no `source`, i.e. the project itself) and
+/// looking for the exact `"name version"` or `"name version (source)"` entry
+/// it declares in its own `dependencies` list.
+fn resolve_via_root_dependencies(
+    packages: &toml_edit::ArrayOfTables,
+    dependency_name: &str,
+) -> Option<String> {
+    let root = packages
+        .iter()
+        .find(|package| package.get("source").is_none())?;
+    let deps = root.get("dependencies")?.as_array()?;
+    deps.iter().find_map(|dep| {
+        let dep_str = dep.as_str()?;
+        let mut parts = dep_str.split_whitespace();
+        if parts.next()? != dependency_name {
+            return None;
+        }
+        parts.next().map(str::to_string)
+    })
+}
+
+/// Rewrites the `features` array of `dependency_name` to `features` in every
+/// `[dependencies]`-shaped table in `doc`: the top-level `[dependencies]`
+/// table and every `[target.'cfg(...)'.dependencies]` table. Returns whether
+/// anything was changed.
+pub fn write_features(
+    doc: &mut DocumentMut,
+    dependency_name: &str,
+    features: &BTreeSet<String>,
+    mode: WriteMode,
+) -> Result<WriteOutcome> {
+    let mut found = false;
+    let mut changed = false;
+
+    if let Some(deps) = doc
+        .get_mut("dependencies")
+        .and_then(Item::as_table_like_mut)
+    {
+        if let Some(table_changed) =
+            apply_to_dependencies_table(deps, dependency_name, features, mode)?
+        {
+            found = true;
+            changed |= table_changed;
+        }
+    }
+
+    if let Some(targets) = doc.get_mut("target").and_then(Item::as_table_like_mut) {
+        for (_cfg, target_item) in targets.iter_mut() {
+            if let Some(deps) = target_item
+                .get_mut("dependencies")
+                .and_then(Item::as_table_like_mut)
+            {
+                if let Some(table_changed) =
+                    apply_to_dependencies_table(deps, dependency_name, features, mode)?
+                {
+                    found = true;
+                    changed |= table_changed;
+                }
+            }
+        }
+    }
+
+    if !found {
+        warn!(
+            "Dependency `{}` was not found in any [dependencies] table",
+            dependency_name
+        );
+        return Ok(WriteOutcome::NotFound);
+    }
+
+    if !changed {
+        return Ok(WriteOutcome::AlreadyUpToDate);
+    }
+
+    Ok(WriteOutcome::Updated)
+}
+
+/// Returns `None` if `dependency_name` isn't declared in `deps`, otherwise
+/// `Some(changed)` for whether its `features` array was updated.
+fn apply_to_dependencies_table(
+    deps: &mut dyn TableLike,
+    dependency_name: &str,
+    features: &BTreeSet<String>,
+    mode: WriteMode,
+) -> Result<Option<bool>> {
+    let Some(dep_item) = deps.get_mut(dependency_name) else {
+        return Ok(None);
+    };
+    set_features(dep_item, features, mode).map(Some)
+}
+
+/// Sets the `features` array on a dependency item, handling the inline-table
+/// (`windows = { version = "...", features = [...] }`), the bare-version
+/// (`windows = "0.58"`), and the detailed-table (`[dependencies.windows]`)
+/// forms.
+fn set_features(item: &mut Item, features: &BTreeSet<String>, mode: WriteMode) -> Result<bool> {
+    let existing = existing_features(item);
+    let merged: BTreeSet<String> = match mode {
+        WriteMode::Replace => features.clone(),
+        WriteMode::Merge => existing.union(features).cloned().collect(),
+    };
+
+    if merged == existing {
+        return Ok(false);
+    }
+
+    let mut array = Array::new();
+    for feature in &merged {
+        array.push(feature.as_str());
+    }
+
+    match item {
+        Item::Value(Value::String(version)) => {
+            let mut inline = toml_edit::InlineTable::new();
+            inline.insert("version", Value::from(version.value().as_str()));
+            inline.insert("features", Value::Array(array));
+            *item = Item::Value(Value::InlineTable(inline));
+        }
+        Item::Value(Value::InlineTable(table)) => {
+            table.insert("features", Value::Array(array));
+        }
+        Item::Table(table) => {
+            table.insert("features", Item::Value(Value::Array(array)));
+        }
+        other => return Err(eyre!("Unsupported dependency item shape: {:?}", other)),
+    }
+
+    Ok(true)
+}
+
+/// Reads the `features` currently declared for `dependency_name` across
+/// every `[dependencies]`-shaped table in `doc`, mirroring the tables
+/// [`write_features`] updates.
+pub fn read_features(doc: &DocumentMut, dependency_name: &str) -> BTreeSet<String> {
+    let mut declared = BTreeSet::new();
+
+    if let Some(deps) = doc.get("dependencies").and_then(Item::as_table_like) {
+        if let Some(dep_item) = deps.get(dependency_name) {
+            declared.extend(existing_features(dep_item));
+        }
+    }
+
+    if let Some(targets) = doc.get("target").and_then(Item::as_table_like) {
+        for (_cfg, target_item) in targets.iter() {
+            if let Some(deps) = target_item
+                .get("dependencies")
+                .and_then(Item::as_table_like)
+            {
+                if let Some(dep_item) = deps.get(dependency_name) {
+                    declared.extend(existing_features(dep_item));
+                }
+            }
+        }
+    }
+
+    declared
+}
+
+/// The difference between what a manifest declares and what the scanned
+/// imports actually require.
+#[derive(Debug, Clone, Default)]
+pub struct FeatureDrift {
+    /// Used by imports but not declared in the manifest.
+    pub missing: BTreeSet<String>,
+    /// Declared in the manifest but no longer referenced by any import.
+    pub superfluous: BTreeSet<String>,
+}
+
+impl FeatureDrift {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.superfluous.is_empty()
+    }
+}
+
+/// Compares `declared` against `required`, producing the missing and
+/// superfluous feature sets.
+pub fn check_features(declared: &BTreeSet<String>, required: &BTreeSet<String>) -> FeatureDrift {
+    FeatureDrift {
+        missing: required.difference(declared).cloned().collect(),
+        superfluous: declared.difference(required).cloned().collect(),
+    }
+}
+
+fn existing_features(item: &Item) -> BTreeSet<String> {
+    let array = match item {
+        Item::Value(Value::InlineTable(table)) => table.get("features").and_then(Value::as_array),
+        Item::Table(table) => table.get("features").and_then(Item::as_array),
+        _ => None,
+    };
+
+    array
+        .into_iter()
+        .flat_map(|array| array.iter())
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn features(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn write_features_updates_bare_version_dependency() {
+        let mut doc: DocumentMut = "[dependencies]\nwindows = \"0.58\"\n".parse().unwrap();
+        let outcome = write_features(
+            &mut doc,
+            "windows",
+            &features(&["Win32_Foundation"]),
+            WriteMode::Merge,
+        )
+        .unwrap();
+        assert_eq!(outcome, WriteOutcome::Updated);
+        assert_eq!(
+            read_features(&doc, "windows"),
+            features(&["Win32_Foundation"])
+        );
+    }
+
+    #[test]
+    fn write_features_updates_inline_table_dependency() {
+        let mut doc: DocumentMut =
+            "[dependencies]\nwindows = { version = \"0.58\", features = [\"A\"] }\n"
+                .parse()
+                .unwrap();
+        let outcome =
+            write_features(&mut doc, "windows", &features(&["B"]), WriteMode::Replace).unwrap();
+        assert_eq!(outcome, WriteOutcome::Updated);
+        assert_eq!(read_features(&doc, "windows"), features(&["B"]));
+    }
+
+    #[test]
+    fn write_features_updates_detailed_table_dependency() {
+        let mut doc: DocumentMut =
+            "[dependencies.windows]\nversion = \"0.58\"\nfeatures = [\"A\"]\n"
+                .parse()
+                .unwrap();
+        let outcome = write_features(
+            &mut doc,
+            "windows",
+            &features(&["A", "B"]),
+            WriteMode::Merge,
+        )
+        .unwrap();
+        assert_eq!(outcome, WriteOutcome::Updated);
+        assert_eq!(read_features(&doc, "windows"), features(&["A", "B"]));
+    }
+
+    #[test]
+    fn write_features_finds_target_cfg_dependency() {
+        let mut doc: DocumentMut = "[target.'cfg(windows)'.dependencies]\nwindows = \"0.58\"\n"
+            .parse()
+            .unwrap();
+        let outcome =
+            write_features(&mut doc, "windows", &features(&["A"]), WriteMode::Merge).unwrap();
+        assert_eq!(outcome, WriteOutcome::Updated);
+        assert_eq!(read_features(&doc, "windows"), features(&["A"]));
+    }
+
+    #[test]
+    fn write_features_merge_unions_with_existing() {
+        let mut doc: DocumentMut =
+            "[dependencies]\nwindows = { version = \"0.58\", features = [\"A\"] }\n"
+                .parse()
+                .unwrap();
+        write_features(&mut doc, "windows", &features(&["B"]), WriteMode::Merge).unwrap();
+        assert_eq!(read_features(&doc, "windows"), features(&["A", "B"]));
+    }
+
+    #[test]
+    fn write_features_replace_prunes_existing() {
+        let mut doc: DocumentMut =
+            "[dependencies]\nwindows = { version = \"0.58\", features = [\"A\"] }\n"
+                .parse()
+                .unwrap();
+        write_features(&mut doc, "windows", &features(&["B"]), WriteMode::Replace).unwrap();
+        assert_eq!(read_features(&doc, "windows"), features(&["B"]));
+    }
+
+    #[test]
+    fn write_features_already_up_to_date() {
+        let mut doc: DocumentMut =
+            "[dependencies]\nwindows = { version = \"0.58\", features = [\"A\"] }\n"
+                .parse()
+                .unwrap();
+        let outcome =
+            write_features(&mut doc, "windows", &features(&["A"]), WriteMode::Merge).unwrap();
+        assert_eq!(outcome, WriteOutcome::AlreadyUpToDate);
+    }
+
+    #[test]
+    fn write_features_not_found_when_dependency_absent() {
+        let mut doc: DocumentMut = "[dependencies]\nserde = \"1\"\n".parse().unwrap();
+        let outcome =
+            write_features(&mut doc, "windows", &features(&["A"]), WriteMode::Merge).unwrap();
+        assert_eq!(outcome, WriteOutcome::NotFound);
+    }
+
+    #[test]
+    fn check_features_reports_missing_and_superfluous() {
+        let drift = check_features(&features(&["A", "B"]), &features(&["B", "C"]));
+        assert_eq!(drift.missing, features(&["C"]));
+        assert_eq!(drift.superfluous, features(&["A"]));
+        assert!(!drift.is_clean());
+    }
+
+    #[test]
+    fn check_features_clean_when_matching() {
+        let drift = check_features(&features(&["A"]), &features(&["A"]));
+        assert!(drift.is_clean());
+    }
+
+    #[test]
+    fn resolve_locked_version_reads_matching_package() {
+        let doc: DocumentMut = "[[package]]\nname = \"windows\"\nversion = \"0.58.0\"\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            resolve_locked_version(&doc, "windows"),
+            Some("0.58.0".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_locked_version_disambiguates_via_root_dependencies() {
+        let doc: DocumentMut = r#"
+[[package]]
+name = "my-crate"
+version = "0.1.0"
+dependencies = [
+ "windows 0.58.0",
+]
+
+[[package]]
+name = "windows"
+version = "0.52.0"
+
+[[package]]
+name = "windows"
+version = "0.58.0"
+"#
+        .parse()
+        .unwrap();
+        assert_eq!(
+            resolve_locked_version(&doc, "windows"),
+            Some("0.58.0".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_locked_version_falls_back_to_first_when_ambiguous() {
+        let doc: DocumentMut = r#"
+[[package]]
+name = "my-crate"
+version = "0.1.0"
+dependencies = []
+
+[[package]]
+name = "windows"
+version = "0.52.0"
+
+[[package]]
+name = "windows"
+version = "0.58.0"
+"#
+        .parse()
+        .unwrap();
+        assert_eq!(
+            resolve_locked_version(&doc, "windows"),
+            Some("0.52.0".to_string())
+        );
+    }
+}
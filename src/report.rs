@@ -0,0 +1,52 @@
+//! Machine-readable report of the scan: for every import, whether (and how)
+//! it resolved to features, so editors/CI can consume `--format json`
+//! instead of parsing log lines.
+
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// What happened when resolving a single import to features.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ImportOutcome {
+    /// Resolved directly (or, for a wildcard, against at least one item).
+    Resolved { features: BTreeSet<String> },
+    /// The fully-qualified name didn't match, but a did-you-mean correction
+    /// was unambiguous and got applied.
+    Corrected {
+        corrected_to: String,
+        features: BTreeSet<String>,
+    },
+    /// No features found, with any did-you-mean candidates that were too
+    /// ambiguous to auto-apply, and any other locally cached windows-rs
+    /// versions under which this exact name does resolve (feature churn
+    /// across releases).
+    Unresolved {
+        suggestions: Vec<String>,
+        resolved_under_other_versions: Vec<String>,
+    },
+    /// A wildcard import whose namespace prefix matched nothing.
+    EmptyWildcard,
+    /// Excluded by `--include`/`--exclude` namespace filtering.
+    Filtered,
+}
+
+/// One scanned import and what came of it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub file: PathBuf,
+    pub namespace: String,
+    pub item: Option<String>,
+    #[serde(flatten)]
+    pub outcome: ImportOutcome,
+}
+
+/// The full report for a scan: the windows-rs version used, the diagnostics
+/// for every import, and the union of all features they contributed.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureReport {
+    pub windows_version: String,
+    pub imports: Vec<ImportReport>,
+    pub features: BTreeSet<String>,
+}